@@ -1,15 +1,85 @@
 use anyhow::Result;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::collections::VecDeque;
 use std::sync::mpsc;
 use std::time::Duration;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+
+/// How many seconds of raw device audio the streaming ring buffer retains
+/// before old samples are dropped.
+const RING_BUFFER_SECONDS: usize = 10;
+
+/// Half-width (taps per side) of the windowed-sinc resampling kernel.
+const RESAMPLE_TAPS: isize = 32;
+
+/// Normalized sinc, `sin(pi x) / (pi x)`, with the removable singularity at 0.
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-6 {
+        1.0
+    } else {
+        let px = std::f32::consts::PI * x;
+        px.sin() / px
+    }
+}
+
+/// Band-limited resampler built on a windowed-sinc (Lanczos-style) kernel with
+/// a Blackman window and an anti-aliasing low-pass at the lower of the two
+/// Nyquist limits. Replaces the two-sample linear interpolation, which aliased
+/// and smeared high frequencies when decimating 44.1/48 kHz hardware to 16 kHz.
+///
+/// Shared by [`AudioCapture::record`] and the streaming [`AudioStream`] so both
+/// capture paths resample identically.
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let ratio = to_rate as f32 / from_rate as f32;
+    let out_len = (samples.len() as f32 * ratio).round() as usize;
+
+    // When downsampling, tighten the sinc cutoff to the target Nyquist (in input
+    // cycles) so content above it is filtered out instead of folding back.
+    let cutoff = ratio.min(1.0);
+
+    let mut out = Vec::with_capacity(out_len);
+    for i in 0..out_len {
+        let center = i as f32 / ratio;
+        let center_floor = center.floor() as isize;
+
+        let mut acc = 0.0;
+        let mut norm = 0.0;
+        for tap in (center_floor - RESAMPLE_TAPS + 1)..=(center_floor + RESAMPLE_TAPS) {
+            if tap < 0 || tap as usize >= samples.len() {
+                continue;
+            }
+
+            let dist = center - tap as f32;
+            // Blackman window over the kernel support, parameterized to [0, 1].
+            let u = (dist / RESAMPLE_TAPS as f32 + 1.0) * 0.5;
+            let window = 0.42
+                - 0.5 * (2.0 * std::f32::consts::PI * u).cos()
+                + 0.08 * (4.0 * std::f32::consts::PI * u).cos();
+            let weight = cutoff * sinc(cutoff * dist) * window;
+
+            acc += samples[tap as usize] * weight;
+            norm += weight;
+        }
+
+        out.push(if norm.abs() > 1e-6 { acc / norm } else { 0.0 });
+    }
+
+    out
+}
 
 const NOISE_GATE_THRESHOLD: f32 = 0.02;
+const HIGH_PASS_CUTOFF: f32 = 100.0;
+const TARGET_SAMPLE_RATE: u32 = 16000;
 
 pub struct AudioCapture {
     host: cpal::Host,
     recording: Arc<AtomicBool>,
+    high_pass_cutoff: f32,
 }
 
 impl AudioCapture {
@@ -17,9 +87,14 @@ impl AudioCapture {
         Self {
             host: cpal::default_host(),
             recording: Arc::new(AtomicBool::new(false)),
+            high_pass_cutoff: HIGH_PASS_CUTOFF,
         }
     }
 
+    pub fn set_high_pass_cutoff(&mut self, cutoff_hz: f32) {
+        self.high_pass_cutoff = cutoff_hz;
+    }
+
     pub fn start_recording(&self) {
         self.recording.store(true, Ordering::SeqCst);
     }
@@ -40,6 +115,33 @@ impl AudioCapture {
         }
     }
 
+    /// One-pole high-pass filter that strips DC and low-frequency rumble/hum
+    /// (fan noise, handling thumps) so it cannot leak into Whisper or the
+    /// voice detector's energy estimate. Runs over the resampled 16 kHz buffer.
+    fn apply_high_pass(samples: &[f32], cutoff_hz: f32, sample_rate: u32) -> Vec<f32> {
+        if samples.is_empty() || cutoff_hz <= 0.0 {
+            return samples.to_vec();
+        }
+
+        let dt = 1.0 / sample_rate as f32;
+        let rc = 1.0 / (2.0 * std::f32::consts::PI * cutoff_hz);
+        let alpha = rc / (rc + dt);
+
+        let mut filtered = Vec::with_capacity(samples.len());
+        let mut prev_in = samples[0];
+        let mut prev_out = 0.0;
+        filtered.push(0.0);
+
+        for &sample in &samples[1..] {
+            let out = alpha * (prev_out + sample - prev_in);
+            filtered.push(out);
+            prev_in = sample;
+            prev_out = out;
+        }
+
+        filtered
+    }
+
     fn get_supported_config(device: &cpal::Device) -> Result<cpal::StreamConfig> {
         let default_config = device.default_input_config()?;
         println!("Default input config: {:?}", default_config);
@@ -87,27 +189,54 @@ impl AudioCapture {
         let samples: Vec<f32> = rx.try_iter().collect();
         let original_rate = config.sample_rate.0;
 
-        if original_rate != 16000 {
-            let ratio = 16000.0 / original_rate as f32;
-            let out_len = (samples.len() as f32 * ratio) as usize;
-            let mut resampled = Vec::with_capacity(out_len);
+        let resampled = resample(&samples, original_rate, TARGET_SAMPLE_RATE);
+
+        Ok(Self::apply_high_pass(&resampled, self.high_pass_cutoff, TARGET_SAMPLE_RATE))
+    }
+
+    /// Open a persistent input stream that feeds a ring buffer continuously,
+    /// instead of recording a fixed-duration block and tearing the stream down.
+    /// The streaming recognition loop drains successive windows from the
+    /// returned [`AudioStream`] so audio flows without per-turn WAV round trips.
+    pub fn open_stream(&self) -> Result<AudioStream> {
+        let device = self.host.default_input_device()
+            .ok_or(anyhow::anyhow!("No input device available"))?;
+
+        let config = Self::get_supported_config(&device)?;
+        println!("Using audio config: {:?}", config);
+
+        let device_rate = config.sample_rate.0;
+        let capacity = device_rate as usize * RING_BUFFER_SECONDS;
+        let buffer = Arc::new(Mutex::new(VecDeque::<f32>::with_capacity(capacity)));
 
-            for i in 0..out_len {
-                let pos = i as f32 / ratio;
-                let pos_floor = pos.floor() as usize;
-                if pos_floor >= samples.len() - 1 {
-                    break;
+        let sink = buffer.clone();
+        let err_fn = |err| eprintln!("An error occurred on stream: {}", err);
+
+        let stream = device.build_input_stream(
+            &config,
+            move |data: &[f32], _: &_| {
+                if let Ok(mut buf) = sink.lock() {
+                    for &sample in data {
+                        buf.push_back(Self::apply_noise_gate(sample));
+                        if buf.len() > capacity {
+                            buf.pop_front();
+                        }
+                    }
                 }
-                let fract = pos - pos_floor as f32;
-                let s1 = samples[pos_floor];
-                let s2 = samples[pos_floor + 1];
-                resampled.push(s1 * (1.0 - fract) + s2 * fract);
-            }
+            },
+            err_fn,
+            None,
+        )?;
 
-            Ok(resampled)
-        } else {
-            Ok(samples)
-        }
+        stream.play()?;
+        self.start_recording();
+
+        Ok(AudioStream {
+            _stream: stream,
+            buffer,
+            device_rate,
+            high_pass_cutoff: self.high_pass_cutoff,
+        })
     }
 
     pub fn save_wav(&self, samples: &[f32], path: &str) -> Result<()> {
@@ -128,3 +257,124 @@ impl AudioCapture {
         Ok(())
     }
 }
+
+/// Handle to a live input stream backed by a ring buffer. Dropping it stops
+/// the underlying device stream.
+pub struct AudioStream {
+    _stream: cpal::Stream,
+    buffer: Arc<Mutex<VecDeque<f32>>>,
+    device_rate: u32,
+    high_pass_cutoff: f32,
+}
+
+impl AudioStream {
+    /// Number of raw (device-rate) samples currently buffered.
+    pub fn available(&self) -> usize {
+        self.buffer.lock().map(|b| b.len()).unwrap_or(0)
+    }
+
+    /// Discard everything currently buffered. Called when returning to
+    /// wake-word mode so audio captured during the assistant's own turn
+    /// (including TTS bleeding back through the mic) is not drained as the
+    /// next "wake word" window.
+    pub fn clear(&self) {
+        if let Ok(mut buf) = self.buffer.lock() {
+            buf.clear();
+        }
+    }
+
+    /// Drain every buffered sample, resample to 16 kHz and high-pass filter it,
+    /// returning the window ready for the VAD and Whisper.
+    pub fn take_window(&self) -> Vec<f32> {
+        let raw: Vec<f32> = match self.buffer.lock() {
+            Ok(mut buf) => buf.drain(..).collect(),
+            Err(_) => return Vec::new(),
+        };
+
+        let resampled = resample(&raw, self.device_rate, TARGET_SAMPLE_RATE);
+
+        AudioCapture::apply_high_pass(&resampled, self.high_pass_cutoff, TARGET_SAMPLE_RATE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The two-sample linear interpolation the windowed-sinc path replaced,
+    /// kept here to measure the accuracy improvement against it.
+    fn linear_resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+        let ratio = to_rate as f32 / from_rate as f32;
+        let out_len = (samples.len() as f32 * ratio) as usize;
+        let mut out = Vec::with_capacity(out_len);
+
+        for i in 0..out_len {
+            let pos = i as f32 / ratio;
+            let pos_floor = pos.floor() as usize;
+            if pos_floor >= samples.len() - 1 {
+                break;
+            }
+            let fract = pos - pos_floor as f32;
+            out.push(samples[pos_floor] * (1.0 - fract) + samples[pos_floor + 1] * fract);
+        }
+
+        out
+    }
+
+    fn sine(freq: f32, rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| (2.0 * std::f32::consts::PI * freq * n as f32 / rate as f32).sin())
+            .collect()
+    }
+
+    /// Signal-to-noise ratio in dB of `actual` against an ideal reference,
+    /// measured over an interior window that excludes kernel edge roll-off.
+    fn snr_db(actual: &[f32], reference: &[f32], guard: usize) -> f32 {
+        let end = actual.len().min(reference.len()) - guard;
+        let mut signal = 0.0;
+        let mut noise = 0.0;
+        for i in guard..end {
+            signal += reference[i] * reference[i];
+            let err = actual[i] - reference[i];
+            noise += err * err;
+        }
+        10.0 * (signal / noise).log10()
+    }
+
+    /// Downsampling a signal that carries energy above the target Nyquist is
+    /// where band-limiting matters: the windowed-sinc anti-aliasing pass must
+    /// reject the out-of-band tone, whereas the old linear path folds it back
+    /// into the passband as large error.
+    #[test]
+    fn windowed_sinc_rejects_aliasing_unlike_linear() {
+        let (from_rate, to_rate) = (48_000u32, 16_000u32);
+        let len = 4_800;
+
+        // 1 kHz signal of interest plus an 11 kHz tone above the 8 kHz target
+        // Nyquist that would alias to 5 kHz without filtering.
+        let input: Vec<f32> = (0..len)
+            .map(|n| {
+                let t = n as f32 / from_rate as f32;
+                (2.0 * std::f32::consts::PI * 1_000.0 * t).sin()
+                    + 0.8 * (2.0 * std::f32::consts::PI * 11_000.0 * t).sin()
+            })
+            .collect();
+        let reference = sine(1_000.0, to_rate, 1_600);
+
+        let guard = RESAMPLE_TAPS as usize;
+        let sinc_snr = snr_db(&resample(&input, from_rate, to_rate), &reference, guard);
+        let linear_snr = snr_db(&linear_resample(&input, from_rate, to_rate), &reference, guard);
+
+        assert!(
+            sinc_snr > linear_snr + 30.0,
+            "windowed-sinc SNR {sinc_snr:.1} dB should far exceed linear {linear_snr:.1} dB"
+        );
+        assert!(sinc_snr > 40.0, "windowed-sinc SNR {sinc_snr:.1} dB too low");
+    }
+
+    #[test]
+    fn resample_is_identity_when_rates_match() {
+        let input = sine(440.0, 16_000, 512);
+        assert_eq!(resample(&input, 16_000, 16_000), input);
+    }
+}
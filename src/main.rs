@@ -1,44 +1,112 @@
 mod audio;
 mod speech;
+mod util;
 mod voice_detection;
 
 use anyhow::Result;
-use audio::AudioCapture;
-use speech::SpeechProcessor;
-use voice_detection::VoiceDetector;
+use audio::{AudioCapture, AudioStream};
+use speech::{GoogleTts, LocalTts, SpeechProcessor, TtsEngine};
+use voice_detection::{SileroVoiceDetector, SpeechDetector, VoiceDetector, SILERO_CHUNK_SIZE};
 use serde_json::json;
 use std::collections::VecDeque;
+use std::path::Path;
 use std::time::Duration;
 use tokio;
 
+/// How long each sliding window accumulates audio before it is drained.
+const WINDOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Speech probability above which the neural VAD counts a chunk as voiced.
+const SILERO_SPEECH_THRESHOLD: f32 = 0.5;
+
+/// Consecutive voiced chunks required before a window is treated as speech.
+const SILERO_MIN_SPEECH_CHUNKS: usize = 2;
+
+/// How many leading-silence windows to tolerate before giving up and returning
+/// to wake-word mode, giving the user seconds to start talking after the prompt
+/// (matching the head-room of the old fixed 5 s capture).
+const LEADING_SILENCE_WINDOWS: usize = 10;
+
+/// Default on-disk location of the Silero VAD model; when present the neural
+/// detector is used in place of the energy gate.
+const SILERO_MODEL_PATH: &str = "models/silero_vad.onnx";
+
 #[derive(Debug, PartialEq)]
 enum ConversationState {
     Idle,
     AwaitingWakeWord,
+    Command,
     Listening,
     Processing,
 }
 
 struct VoiceAssistant {
     audio_capture: AudioCapture,
+    audio_stream: Option<AudioStream>,
     speech_processor: SpeechProcessor,
     voice_detector: VoiceDetector,
+    silero: Option<SileroVoiceDetector>,
     is_active: bool,
     state: ConversationState,
     command_history: VecDeque<(String, String)>,
     max_history: usize,
+    commands: Vec<String>,
 }
 
 impl VoiceAssistant {
     async fn new() -> Result<Self> {
+        let commands = SpeechProcessor::load_commands("commands.txt").unwrap_or_default();
+
+        // Prefer the neural Silero VAD when its model is on disk; otherwise fall
+        // back to the dependency-free energy gate.
+        let silero = if Path::new(SILERO_MODEL_PATH).exists() {
+            match SileroVoiceDetector::new(SILERO_MODEL_PATH, 16000) {
+                Ok(detector) => Some(detector),
+                Err(e) => {
+                    eprintln!("Failed to load Silero VAD model ({}); using energy detector", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // Filter cutoff (`freq_thold`) and VAD energy threshold (`vad_thold`)
+        // are tuned independently, each overridable from the environment.
+        let mut audio_capture = AudioCapture::new();
+        if let Some(cutoff) = std::env::var("VA_HIGHPASS_CUTOFF").ok().and_then(|v| v.parse().ok()) {
+            audio_capture.set_high_pass_cutoff(cutoff);
+        }
+
+        let mut voice_detector = VoiceDetector::new(0.02, 0.5, "yo");
+        if let Some(threshold) = std::env::var("VA_VAD_THRESHOLD").ok().and_then(|v| v.parse().ok()) {
+            voice_detector.set_energy_threshold(threshold);
+        }
+
+        // Select the TTS backend at startup: `VA_TTS=local` runs the fully
+        // offline neural synthesizer (local Whisper + local LLM + local TTS);
+        // anything else keeps the Google Translate HTTP fallback.
+        let tts = if std::env::var("VA_TTS").as_deref() == Ok("local") {
+            let acoustic = std::env::var("VA_TTS_ACOUSTIC")
+                .unwrap_or_else(|_| "models/tts_acoustic.onnx".to_string());
+            let vocoder = std::env::var("VA_TTS_VOCODER")
+                .unwrap_or_else(|_| "models/tts_vocoder.onnx".to_string());
+            TtsEngine::Local(LocalTts::new(&acoustic, &vocoder)?)
+        } else {
+            TtsEngine::Google(GoogleTts)
+        };
+
         Ok(Self {
-            audio_capture: AudioCapture::new(),
-            speech_processor: SpeechProcessor::new().await?,
-            voice_detector: VoiceDetector::new(0.02, 0.5, "yo"),
+            audio_capture,
+            audio_stream: None,
+            speech_processor: SpeechProcessor::new(tts).await?,
+            voice_detector,
+            silero,
             is_active: false,
             state: ConversationState::Idle,
             command_history: VecDeque::new(),
             max_history: 10,
+            commands,
         })
     }
 
@@ -69,38 +137,164 @@ impl VoiceAssistant {
             .ok_or_else(|| anyhow::anyhow!("Invalid response format from Ollama"))
     }
 
+    /// Accumulate audio from the live stream until the next window is ready,
+    /// then hand it back resampled and filtered.
+    async fn next_window(&self) -> Vec<f32> {
+        tokio::time::sleep(WINDOW_INTERVAL).await;
+        match &self.audio_stream {
+            Some(stream) => stream.take_window(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Decide whether a window contains speech. When the neural Silero VAD is
+    /// loaded it wins: the window is voiced once the per-chunk probability
+    /// exceeds [`SILERO_SPEECH_THRESHOLD`] for [`SILERO_MIN_SPEECH_CHUNKS`]
+    /// consecutive 512-sample chunks. Otherwise — or if the model errors — the
+    /// energy gate stands in as the fallback.
+    fn window_is_voiced(&mut self, window: &[f32]) -> bool {
+        if let Some(detector) = self.silero.as_mut() {
+            // Feed every full chunk through the model so its recurrent state
+            // stays synchronized with the contiguous audio timeline, then
+            // decide voicing from the longest run of above-threshold chunks.
+            let mut consecutive = 0;
+            let mut max_consecutive = 0;
+            let mut errored = false;
+            for chunk in window.chunks(SILERO_CHUNK_SIZE) {
+                if chunk.len() < SILERO_CHUNK_SIZE {
+                    break;
+                }
+                match detector.predict(chunk) {
+                    Ok(prob) => {
+                        if prob > SILERO_SPEECH_THRESHOLD {
+                            consecutive += 1;
+                            max_consecutive = max_consecutive.max(consecutive);
+                        } else {
+                            consecutive = 0;
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Silero VAD error, falling back to energy gate: {}", e);
+                        errored = true;
+                        break;
+                    }
+                }
+            }
+            if !errored {
+                return max_consecutive >= SILERO_MIN_SPEECH_CHUNKS;
+            }
+        }
+        self.voice_detector.is_voice_active(window, 1024)
+    }
+
+    /// Return to wake-word listening, discarding the recurrent VAD state so the
+    /// next utterance starts with clean temporal context.
+    fn enter_wake_word_mode(&mut self) {
+        self.state = ConversationState::AwaitingWakeWord;
+        self.is_active = false;
+        if let Some(stream) = &self.audio_stream {
+            stream.clear();
+        }
+        if let Some(detector) = self.silero.as_mut() {
+            detector.reset();
+        }
+    }
+
+    /// Accumulate successive windows into a single utterance and let the VAD
+    /// decide where it ends, rather than clipping at a fixed block boundary.
+    /// Returns `None` when only silence was heard, signalling a return to
+    /// wake-word mode.
+    async fn collect_utterance(&mut self) -> Option<Vec<f32>> {
+        let mut utterance: Vec<f32> = Vec::new();
+        let mut leading_silence = 0;
+        loop {
+            let window = self.next_window().await;
+            let voiced = self.window_is_voiced(&window);
+
+            if voiced {
+                utterance.extend_from_slice(&window);
+            } else if !utterance.is_empty() {
+                // Trailing silence after speech marks end-of-utterance.
+                return Some(utterance);
+            } else {
+                // Still waiting for the user to start: allow a grace period of
+                // leading silence before giving up, rather than bouncing back
+                // to wake-word mode after a single quiet window.
+                leading_silence += 1;
+                if leading_silence >= LEADING_SILENCE_WINDOWS {
+                    return None;
+                }
+            }
+        }
+    }
+
     async fn process_interaction(&mut self) -> Result<()> {
         match self.state {
             ConversationState::Idle | ConversationState::AwaitingWakeWord => {
                 print!("\rListening for wake word... (say 'yo')\r");
-                let samples = self.audio_capture.record(Duration::from_secs(2))?;
+                let samples = self.next_window().await;
 
-                if !samples.is_empty() {
-                    self.audio_capture.save_wav(&samples, "wake_word.wav")?;
-                    let wake_word_text = self.speech_processor.speech_to_text("wake_word.wav")?;
+                // Only run Whisper when the window actually carries audio;
+                // transcribing every idle 500 ms window is both a CPU drain and
+                // worse for wake-word accuracy on such short context.
+                if !samples.is_empty() && self.window_is_voiced(&samples) {
+                    let wake_word_text = self.speech_processor.speech_to_text(&samples)?;
 
                     if self.voice_detector.matches_wake_word(&wake_word_text) {
                         println!("\nWake word detected! What can I help you with?");
-                        self.state = ConversationState::Listening;
+                        // Prefer the fast command grammar when one is configured;
+                        // otherwise go straight to free-form dictation.
+                        self.state = if self.commands.is_empty() {
+                            ConversationState::Listening
+                        } else {
+                            ConversationState::Command
+                        };
                         self.is_active = true;
                     }
                 }
             },
+            ConversationState::Command => {
+                println!("Listening for a command...");
+
+                let utterance = match self.collect_utterance().await {
+                    Some(utterance) => utterance,
+                    None => {
+                        println!("\nNo voice detected, returning to wake word mode...");
+                        self.enter_wake_word_mode();
+                        return Ok(());
+                    }
+                };
+
+                let text = self.speech_processor.speech_to_text(&utterance)?;
+
+                if let Some(command) = SpeechProcessor::match_command(&text, &self.commands) {
+                    // Deterministic command — skip the Ollama round-trip entirely.
+                    println!("Command: {}", command);
+                    self.add_to_history(command.clone(), String::new());
+                } else if !text.is_empty() {
+                    // Not in the grammar; treat it as an open-ended request.
+                    println!("You said: {}", text);
+                    let response = self.query_llm(&text).await?;
+                    println!("Assistant: {}", response);
+                    self.add_to_history(text, response.clone());
+                    self.speech_processor.text_to_speech(&response).await?;
+                }
+
+                self.enter_wake_word_mode();
+            },
             ConversationState::Listening => {
                 println!("Listening...");
-                let samples = self.audio_capture.record(Duration::from_secs(5))?;
 
-                if samples.is_empty() || !self.voice_detector.is_voice_active(&samples, 1024) {
-                    if self.voice_detector.detect_silence(&samples, 16000) {
+                let utterance = match self.collect_utterance().await {
+                    Some(utterance) => utterance,
+                    None => {
                         println!("\nNo voice detected, returning to wake word mode...");
-                        self.state = ConversationState::AwaitingWakeWord;
-                        self.is_active = false;
+                        self.enter_wake_word_mode();
+                        return Ok(());
                     }
-                    return Ok(());
-                }
+                };
 
-                self.audio_capture.save_wav(&samples, "input.wav")?;
-                let text = self.speech_processor.speech_to_text("input.wav")?;
+                let text = self.speech_processor.speech_to_text(&utterance)?;
 
                 if !text.is_empty() {
                     println!("You said: {}", text);
@@ -113,8 +307,7 @@ impl VoiceAssistant {
                     self.speech_processor.text_to_speech(&response).await?;
                 }
 
-                self.state = ConversationState::AwaitingWakeWord;
-                self.is_active = false;
+                self.enter_wake_word_mode();
             },
             ConversationState::Processing => {
                 self.state = ConversationState::Listening;
@@ -125,6 +318,7 @@ impl VoiceAssistant {
 
     async fn run(&mut self) -> Result<()> {
         println!("Initializing Voice Assistant...");
+        self.audio_stream = Some(self.audio_capture.open_stream()?);
         self.state = ConversationState::AwaitingWakeWord;
 
         loop {
@@ -132,8 +326,7 @@ impl VoiceAssistant {
                 Ok(_) => (),
                 Err(e) => {
                     eprintln!("Error during interaction: {}", e);
-                    self.state = ConversationState::AwaitingWakeWord;
-                    self.is_active = false;
+                    self.enter_wake_word_mode();
                     tokio::time::sleep(Duration::from_secs(1)).await;
                 }
             }
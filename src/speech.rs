@@ -4,13 +4,142 @@ use std::path::Path;
 use std::fs;
 use reqwest;
 use std::io::{copy, Cursor};
+use rodio::buffer::SamplesBuffer;
 use rodio::{Decoder, OutputStream, Sink};
+use ndarray::{Array1, Array2};
+use ort::{GraphOptimizationLevel, Session};
+use crate::util::levenshtein_distance;
 
 const MODEL_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-tiny.bin";
 const TTS_URL: &str = "https://translate.google.com/translate_tts";
 
+/// Minimum length-normalized similarity a command hypothesis must reach to be
+/// accepted in constrained-grammar mode.
+const COMMAND_CONFIDENCE: f32 = 0.6;
+
+/// Output sample rate of the local neural vocoder.
+const LOCAL_TTS_SAMPLE_RATE: u32 = 22050;
+
+/// A text-to-speech backend. Implementations turn text into audio and play it
+/// through the shared rodio [`Sink`], so the synthesis source (network vs.
+/// local model) is swappable without touching the rest of the pipeline.
+#[allow(async_fn_in_trait)]
+pub trait TtsBackend {
+    async fn synthesize(&self, text: &str, sink: &Sink) -> Result<()>;
+}
+
+/// The original backend: fetches MP3 from the Google Translate TTS endpoint.
+/// Fragile and network-bound, kept as a fallback.
+pub struct GoogleTts;
+
+impl TtsBackend for GoogleTts {
+    async fn synthesize(&self, text: &str, sink: &Sink) -> Result<()> {
+        let chunks: Vec<&str> = text.split(|c| c == '.' || c == '?' || c == '!')
+            .filter(|s| !s.trim().is_empty())
+            .collect();
+
+        let client = reqwest::Client::new();
+
+        for chunk in chunks {
+            let text = chunk.trim();
+            if text.is_empty() {
+                continue;
+            }
+
+            let response = client.get(TTS_URL)
+                .query(&[
+                    ("ie", "UTF-8"),
+                    ("tl", "en"),
+                    ("q", text),
+                    ("client", "tw-ob"),
+                ])
+                .header("User-Agent", "Mozilla/5.0")
+                .send()
+                .await?;
+
+            let audio_data = response.bytes().await?;
+            let source = Decoder::new(Cursor::new(audio_data))?;
+            sink.append(source);
+        }
+
+        Ok(())
+    }
+}
+
+/// Fully local synthesis: an acoustic model turns text into a mel spectrogram
+/// which a neural vocoder (HiFi-GAN style) converts to a waveform, letting the
+/// assistant run offline end-to-end with no network round trip.
+pub struct LocalTts {
+    acoustic: Session,
+    vocoder: Session,
+}
+
+impl LocalTts {
+    pub fn new(acoustic_path: &str, vocoder_path: &str) -> Result<Self> {
+        let acoustic = Session::builder()?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .commit_from_file(acoustic_path)?;
+        let vocoder = Session::builder()?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .commit_from_file(vocoder_path)?;
+
+        Ok(Self { acoustic, vocoder })
+    }
+
+    /// Naive character-level tokenizer; real models ship a phonemizer, but this
+    /// keeps the input contract (a sequence of token ids) explicit.
+    fn tokenize(text: &str) -> Vec<i64> {
+        text.to_lowercase()
+            .chars()
+            .map(|c| c as i64)
+            .collect()
+    }
+}
+
+impl TtsBackend for LocalTts {
+    async fn synthesize(&self, text: &str, sink: &Sink) -> Result<()> {
+        let tokens = Self::tokenize(text);
+        if tokens.is_empty() {
+            return Ok(());
+        }
+
+        let input = Array2::from_shape_vec((1, tokens.len()), tokens)?;
+        let mel_outputs = self.acoustic.run(ort::inputs!["tokens" => input.view()]?)?;
+        let mel = mel_outputs["mel"].try_extract_tensor::<f32>()?.to_owned();
+
+        let wav_outputs = self.vocoder.run(ort::inputs!["mel" => mel.view()]?)?;
+        let waveform: Array1<f32> = wav_outputs["waveform"]
+            .try_extract_tensor::<f32>()?
+            .iter()
+            .copied()
+            .collect();
+
+        let buffer = SamplesBuffer::new(1, LOCAL_TTS_SAMPLE_RATE, waveform.to_vec());
+        sink.append(buffer);
+
+        Ok(())
+    }
+}
+
+/// Backend selected at [`SpeechProcessor::new`] time. Dispatches to whichever
+/// [`TtsBackend`] the assistant was configured with.
+pub enum TtsEngine {
+    Google(GoogleTts),
+    Local(LocalTts),
+}
+
+impl TtsBackend for TtsEngine {
+    async fn synthesize(&self, text: &str, sink: &Sink) -> Result<()> {
+        match self {
+            TtsEngine::Google(backend) => backend.synthesize(text, sink).await,
+            TtsEngine::Local(backend) => backend.synthesize(text, sink).await,
+        }
+    }
+}
+
 pub struct SpeechProcessor {
     whisper_ctx: WhisperContext,
+    tts: TtsEngine,
 }
 
 impl SpeechProcessor {
@@ -23,7 +152,7 @@ impl SpeechProcessor {
         Ok(())
     }
 
-    pub async fn new() -> Result<Self> {
+    pub async fn new(tts: TtsEngine) -> Result<Self> {
         let model_path = "models/ggml-tiny.bin";
         if !Path::new(model_path).exists() {
             Self::download_model(model_path).await?;
@@ -33,25 +162,34 @@ impl SpeechProcessor {
 
         Ok(Self {
             whisper_ctx: ctx,
+            tts,
         })
     }
 
-    pub fn speech_to_text(&self, audio_path: &str) -> Result<String> {
-        let audio_data = self.load_audio(audio_path)?;
+    /// Transcribe an accumulated utterance held entirely in memory.
+    ///
+    /// A fresh [`WhisperState`] is created per utterance from the long-lived
+    /// context — keeping the context and a borrowing state in one struct is the
+    /// whisper_rs self-referential hazard — and `set_no_context(true)` keeps
+    /// each utterance independent so decoding never drags in prior turns. This
+    /// is the path the streaming loop feeds successive windows into, replacing
+    /// the old per-call temp-WAV round trips.
+    pub fn speech_to_text(&self, samples: &[f32]) -> Result<String> {
         let mut state = self.whisper_ctx.create_state()?;
-        
+
         let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 3 });
         params.set_translate(false);
         params.set_language(Some("en"));
+        params.set_no_context(true);
         params.set_print_special(false);
         params.set_print_progress(false);
         params.set_print_timestamps(false);
-        
-        state.full(params, &audio_data[..])?;
-        
+
+        state.full(params, samples)?;
+
         let num_segments = state.full_n_segments()?;
         let mut text = String::new();
-        
+
         for i in 0..num_segments {
             if let Ok(segment) = state.full_get_segment_text(i) {
                 let cleaned = segment.replace("[noise]", "")
@@ -69,47 +207,45 @@ impl SpeechProcessor {
         Ok(text.trim().to_string())
     }
 
-    fn load_audio(&self, path: &str) -> Result<Vec<f32>> {
-        let mut reader = hound::WavReader::open(path)?;
-        let samples: Vec<f32> = reader.samples::<i16>()
-            .filter_map(Result::ok)
-            .map(|s| s as f32 / i16::MAX as f32)
-            .collect();
-        Ok(samples)
+    /// Load the allowed-command grammar, one command per line, lower-cased.
+    pub fn load_commands(path: &str) -> Result<Vec<String>> {
+        let contents = fs::read_to_string(path)?;
+        Ok(contents
+            .lines()
+            .map(|line| line.trim().to_lowercase())
+            .filter(|line| !line.is_empty())
+            .collect())
     }
 
-    pub async fn text_to_speech(&self, text: &str) -> Result<()> {
-        let chunks: Vec<&str> = text.split(|c| c == '.' || c == '?' || c == '!')
-            .filter(|s| !s.trim().is_empty())
-            .collect();
+    /// Snap a free-form hypothesis to the closest allowed command, scoring each
+    /// with the shared Levenshtein distance normalized by length. Returns the
+    /// best match scoring at least [`COMMAND_CONFIDENCE`], or `None` when nothing is close
+    /// enough — letting the caller fall back to the open-ended LLM path.
+    pub fn match_command(hypothesis: &str, commands: &[String]) -> Option<String> {
+        let hypothesis = hypothesis.trim().to_lowercase();
+        if hypothesis.is_empty() {
+            return None;
+        }
 
-        let client = reqwest::Client::new();
-        let (_stream, stream_handle) = OutputStream::try_default()?;
-        let sink = Sink::try_new(&stream_handle)?;
+        let mut best: Option<(f32, &String)> = None;
+        for command in commands {
+            let distance = levenshtein_distance(&hypothesis, command);
+            let max_len = hypothesis.chars().count().max(command.chars().count()).max(1);
+            let score = 1.0 - distance as f32 / max_len as f32;
 
-        for chunk in chunks {
-            let text = chunk.trim();
-            if text.is_empty() {
-                continue;
+            if score >= COMMAND_CONFIDENCE && best.map_or(true, |(b, _)| score > b) {
+                best = Some((score, command));
             }
+        }
 
-            let response = client.get(TTS_URL)
-                .query(&[
-                    ("ie", "UTF-8"),
-                    ("tl", "en"),
-                    ("q", text),
-                    ("client", "tw-ob"),
-                ])
-                .header("User-Agent", "Mozilla/5.0")
-                .send()
-                .await?;
+        best.map(|(_, command)| command.clone())
+    }
 
-            let audio_data = response.bytes().await?;
-            let cursor = Cursor::new(audio_data);
+    pub async fn text_to_speech(&self, text: &str) -> Result<()> {
+        let (_stream, stream_handle) = OutputStream::try_default()?;
+        let sink = Sink::try_new(&stream_handle)?;
 
-            let source = Decoder::new(cursor)?;
-            sink.append(source);
-        }
+        self.tts.synthesize(text, &sink).await?;
 
         sink.sleep_until_end();
         Ok(())
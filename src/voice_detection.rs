@@ -1,5 +1,88 @@
 use anyhow::Result;
+use ndarray::{Array1, Array2, Array3};
+use ort::{inputs, GraphOptimizationLevel, Session};
 use std::collections::VecDeque;
+use std::path::Path;
+
+/// Chunk size (in samples) the Silero VAD model expects at 16 kHz.
+pub const SILERO_CHUNK_SIZE: usize = 512;
+
+/// A per-chunk speech detector. Implementations return a probability in
+/// `[0.0, 1.0]` that the chunk contains speech; the energy detector remains
+/// available as a dependency-free fallback.
+pub trait SpeechDetector {
+    /// Run the detector over a single fixed-size chunk of 16 kHz audio and
+    /// return the probability that it contains speech.
+    fn predict(&mut self, chunk: &[f32]) -> Result<f32>;
+
+    /// Discard any temporal state, e.g. when returning to wake-word mode.
+    fn reset(&mut self);
+}
+
+/// Neural voice-activity detector backed by the Silero VAD ONNX model.
+///
+/// The model is an LSTM-style network: each call feeds a fixed chunk of
+/// samples plus the recurrent state tensors `h`/`c` and reads back a scalar
+/// speech probability together with the updated state, so temporal context
+/// carries across chunks.
+pub struct SileroVoiceDetector {
+    session: Session,
+    sample_rate: i64,
+    h: Array3<f32>,
+    c: Array3<f32>,
+}
+
+impl SileroVoiceDetector {
+    pub fn new(model_path: impl AsRef<Path>, sample_rate: u32) -> Result<Self> {
+        let session = Session::builder()?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .commit_from_file(model_path)?;
+
+        Ok(Self {
+            session,
+            sample_rate: sample_rate as i64,
+            h: Array3::zeros((2, 1, 64)),
+            c: Array3::zeros((2, 1, 64)),
+        })
+    }
+}
+
+impl SpeechDetector for SileroVoiceDetector {
+    fn predict(&mut self, chunk: &[f32]) -> Result<f32> {
+        let input = Array2::from_shape_vec((1, chunk.len()), chunk.to_vec())?;
+        let sr = Array1::from_elem(1, self.sample_rate);
+
+        let outputs = self.session.run(inputs![
+            "input" => input.view(),
+            "sr" => sr.view(),
+            "h" => self.h.view(),
+            "c" => self.c.view(),
+        ]?)?;
+
+        self.h = outputs["hn"]
+            .try_extract_tensor::<f32>()?
+            .to_owned()
+            .into_dimensionality()?;
+        self.c = outputs["cn"]
+            .try_extract_tensor::<f32>()?
+            .to_owned()
+            .into_dimensionality()?;
+
+        let prob = outputs["output"]
+            .try_extract_tensor::<f32>()?
+            .iter()
+            .next()
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Silero VAD returned no output"))?;
+
+        Ok(prob)
+    }
+
+    fn reset(&mut self) {
+        self.h.fill(0.0);
+        self.c.fill(0.0);
+    }
+}
 
 pub struct VoiceDetector {
     energy_threshold: f32,
@@ -24,6 +107,13 @@ impl VoiceDetector {
         }
     }
 
+    /// Set the VAD energy threshold (the `vad_thold` analog) independently of
+    /// the high-pass cutoff (`freq_thold`) so the gate can be tuned for the
+    /// filtered signal without touching the filter.
+    pub fn set_energy_threshold(&mut self, threshold: f32) {
+        self.energy_threshold = threshold;
+    }
+
     pub fn calculate_rms(samples: &[f32]) -> f32 {
         if samples.is_empty() {
             return 0.0;
@@ -78,31 +168,6 @@ impl VoiceDetector {
         !self.is_voice_active(samples, window_size)
     }
 
-    fn levenshtein_distance(s1: &str, s2: &str) -> usize {
-        let len1 = s1.chars().count();
-        let len2 = s2.chars().count();
-
-        let mut matrix = vec![vec![0; len2 + 1]; len1 + 1];
-
-        for i in 0..=len1 {
-            matrix[i][0] = i;
-        }
-        for j in 0..=len2 {
-            matrix[0][j] = j;
-        }
-
-        for (i, c1) in s1.chars().enumerate() {
-            for (j, c2) in s2.chars().enumerate() {
-                let cost = if c1 == c2 { 0 } else { 1 };
-                matrix[i + 1][j + 1] = (matrix[i][j + 1] + 1)
-                    .min(matrix[i + 1][j] + 1)
-                    .min(matrix[i][j] + cost);
-            }
-        }
-
-        matrix[len1][len2]
-    }
-
     pub fn matches_wake_word(&self, text: &str) -> bool {
         let text = text.to_lowercase();
         let words: Vec<&str> = text.split_whitespace().collect();
@@ -113,7 +178,7 @@ impl VoiceDetector {
 
         for word in words {
             if word.len() >= 2 &&
-               Self::levenshtein_distance(word, &self.activation_word) <= 1 {
+               crate::util::levenshtein_distance(word, &self.activation_word) <= 1 {
                 return true;
             }
         }